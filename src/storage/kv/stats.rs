@@ -1,8 +1,13 @@
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
+use std::cell::Cell;
+
+use engine_rocksdb::{set_perf_level, PerfContext, PerfLevel};
 use engine_traits::{CF_DEFAULT, CF_LOCK, CF_WRITE};
 use kvproto::kvrpcpb::{ScanDetail, ScanInfo};
 
+use crate::storage::metrics::{GcKeysCF, GcKeysDetail};
+
 pub use raftstore::store::{FlowStatistics, FlowStatsReporter};
 
 const STAT_PROCESSED_KEYS: &str = "processed_keys";
@@ -12,6 +17,12 @@ const STAT_PREV: &str = "prev";
 const STAT_SEEK: &str = "seek";
 const STAT_SEEK_FOR_PREV: &str = "seek_for_prev";
 const STAT_OVER_SEEK_BOUND: &str = "over_seek_bound";
+const STAT_BLOCK_CACHE_HIT_COUNT: &str = "block_cache_hit_count";
+const STAT_BLOCK_READ_COUNT: &str = "block_read_count";
+const STAT_BLOOM_FILTER_CHECKED: &str = "bloom_filter_checked";
+const STAT_BLOOM_FILTER_USEFUL: &str = "bloom_filter_useful";
+const STAT_INTERNAL_KEY_SKIPPED_COUNT: &str = "internal_key_skipped_count";
+const STAT_INTERNAL_DELETE_SKIPPED_COUNT: &str = "internal_delete_skipped_count";
 
 /// Statistics collects the ops taken when fetching data.
 #[derive(Default, Clone, Debug)]
@@ -26,6 +37,19 @@ pub struct CfStatistics {
     pub seek_for_prev: usize,
     pub over_seek_bound: usize,
 
+    // Physical read effort reported by RocksDB's thread-local PerfContext. These tell us how
+    // much work the storage engine actually did (blocks touched, bloom filter efficacy,
+    // tombstones walked over) even when the logical op counts look unremarkable, which is the
+    // usual symptom of a slow scan with poor cache locality. They are only filled in where a
+    // caller brackets an operation with `start_perf_context`/`record_perf_context`; the
+    // iterators and snapshots that count the logical ops above do not record them.
+    pub block_cache_hit_count: usize,
+    pub block_read_count: usize,
+    pub bloom_filter_checked: usize,
+    pub bloom_filter_useful: usize,
+    pub internal_key_skipped_count: usize,
+    pub internal_delete_skipped_count: usize,
+
     pub flow_stats: FlowStatistics,
 }
 
@@ -35,7 +59,7 @@ impl CfStatistics {
         self.get + self.next + self.prev + self.seek + self.seek_for_prev
     }
 
-    pub fn details(&self) -> [(&'static str, usize); 7] {
+    pub fn details(&self) -> [(&'static str, usize); 13] {
         [
             (STAT_PROCESSED_KEYS, self.processed_keys),
             (STAT_GET, self.get),
@@ -44,12 +68,22 @@ impl CfStatistics {
             (STAT_SEEK, self.seek),
             (STAT_SEEK_FOR_PREV, self.seek_for_prev),
             (STAT_OVER_SEEK_BOUND, self.over_seek_bound),
+            (STAT_BLOCK_CACHE_HIT_COUNT, self.block_cache_hit_count),
+            (STAT_BLOCK_READ_COUNT, self.block_read_count),
+            (STAT_BLOOM_FILTER_CHECKED, self.bloom_filter_checked),
+            (STAT_BLOOM_FILTER_USEFUL, self.bloom_filter_useful),
+            (
+                STAT_INTERNAL_KEY_SKIPPED_COUNT,
+                self.internal_key_skipped_count,
+            ),
+            (
+                STAT_INTERNAL_DELETE_SKIPPED_COUNT,
+                self.internal_delete_skipped_count,
+            ),
         ]
     }
 
-<<<<<<< HEAD
-=======
-    pub fn details_enum(&self) -> [(GcKeysDetail, usize); 7] {
+    pub fn details_enum(&self) -> [(GcKeysDetail, usize); 13] {
         [
             (GcKeysDetail::processed_keys, self.processed_keys),
             (GcKeysDetail::get, self.get),
@@ -58,10 +92,27 @@ impl CfStatistics {
             (GcKeysDetail::seek, self.seek),
             (GcKeysDetail::seek_for_prev, self.seek_for_prev),
             (GcKeysDetail::over_seek_bound, self.over_seek_bound),
+            (
+                GcKeysDetail::block_cache_hit_count,
+                self.block_cache_hit_count,
+            ),
+            (GcKeysDetail::block_read_count, self.block_read_count),
+            (
+                GcKeysDetail::bloom_filter_checked,
+                self.bloom_filter_checked,
+            ),
+            (GcKeysDetail::bloom_filter_useful, self.bloom_filter_useful),
+            (
+                GcKeysDetail::internal_key_skipped_count,
+                self.internal_key_skipped_count,
+            ),
+            (
+                GcKeysDetail::internal_delete_skipped_count,
+                self.internal_delete_skipped_count,
+            ),
         ]
     }
 
->>>>>>> 790f53e... Fix incorrect processed / total keys counter (#7563)
     pub fn add(&mut self, other: &Self) {
         self.processed_keys = self.processed_keys.saturating_add(other.processed_keys);
         self.get = self.get.saturating_add(other.get);
@@ -70,9 +121,53 @@ impl CfStatistics {
         self.seek = self.seek.saturating_add(other.seek);
         self.seek_for_prev = self.seek_for_prev.saturating_add(other.seek_for_prev);
         self.over_seek_bound = self.over_seek_bound.saturating_add(other.over_seek_bound);
+        self.block_cache_hit_count = self
+            .block_cache_hit_count
+            .saturating_add(other.block_cache_hit_count);
+        self.block_read_count = self.block_read_count.saturating_add(other.block_read_count);
+        self.bloom_filter_checked = self
+            .bloom_filter_checked
+            .saturating_add(other.bloom_filter_checked);
+        self.bloom_filter_useful = self
+            .bloom_filter_useful
+            .saturating_add(other.bloom_filter_useful);
+        self.internal_key_skipped_count = self
+            .internal_key_skipped_count
+            .saturating_add(other.internal_key_skipped_count);
+        self.internal_delete_skipped_count = self
+            .internal_delete_skipped_count
+            .saturating_add(other.internal_delete_skipped_count);
         self.flow_stats.add(&other.flow_stats);
     }
 
+    /// Snapshots RocksDB's thread-local PerfContext counters so the delta observed across a
+    /// scan or point-get can be folded into this CfStatistics once the caller is done with the
+    /// iterator or snapshot.
+    pub fn start_perf_context() -> PerfContextStats {
+        PerfContextStats::capture()
+    }
+
+    /// Folds the physical read effort observed since `start` into this CfStatistics.
+    pub fn record_perf_context(&mut self, start: PerfContextStats) {
+        let delta = start.delta();
+        self.block_cache_hit_count = self
+            .block_cache_hit_count
+            .saturating_add(delta.block_cache_hit_count);
+        self.block_read_count = self.block_read_count.saturating_add(delta.block_read_count);
+        self.bloom_filter_checked = self
+            .bloom_filter_checked
+            .saturating_add(delta.bloom_filter_checked);
+        self.bloom_filter_useful = self
+            .bloom_filter_useful
+            .saturating_add(delta.bloom_filter_useful);
+        self.internal_key_skipped_count = self
+            .internal_key_skipped_count
+            .saturating_add(delta.internal_key_skipped_count);
+        self.internal_delete_skipped_count = self
+            .internal_delete_skipped_count
+            .saturating_add(delta.internal_delete_skipped_count);
+    }
+
     /// Deprecated
     pub fn scan_info(&self) -> ScanInfo {
         let mut info = ScanInfo::default();
@@ -90,7 +185,7 @@ pub struct Statistics {
 }
 
 impl Statistics {
-    pub fn details(&self) -> [(&'static str, [(&'static str, usize); 7]); 3] {
+    pub fn details(&self) -> [(&'static str, [(&'static str, usize); 13]); 3] {
         [
             (CF_DEFAULT, self.data.details()),
             (CF_LOCK, self.lock.details()),
@@ -98,9 +193,7 @@ impl Statistics {
         ]
     }
 
-<<<<<<< HEAD
-=======
-    pub fn details_enum(&self) -> [(GcKeysCF, [(GcKeysDetail, usize); 7]); 3] {
+    pub fn details_enum(&self) -> [(GcKeysCF, [(GcKeysDetail, usize); 13]); 3] {
         [
             (GcKeysCF::default, self.data.details_enum()),
             (GcKeysCF::lock, self.lock.details_enum()),
@@ -108,7 +201,6 @@ impl Statistics {
         ]
     }
 
->>>>>>> 790f53e... Fix incorrect processed / total keys counter (#7563)
     pub fn add(&mut self, other: &Self) {
         self.lock.add(&other.lock);
         self.write.add(&other.write);
@@ -149,3 +241,101 @@ impl StatisticsSummary {
         self.count += 1;
     }
 }
+
+/// A point-in-time snapshot of RocksDB's thread-local PerfContext, taken at the start of a
+/// scan or point-get. Pass it to `CfStatistics::record_perf_context` once the operation is
+/// done to fold in the counters it alone is responsible for.
+#[derive(Default, Clone, Copy)]
+pub struct PerfContextStats {
+    block_cache_hit_count: usize,
+    block_read_count: usize,
+    bloom_filter_checked: usize,
+    bloom_filter_useful: usize,
+    internal_key_skipped_count: usize,
+    internal_delete_skipped_count: usize,
+}
+
+thread_local! {
+    // `set_perf_level` only affects the calling thread's PerfContext, so a process-wide
+    // `Once` would leave every thread but the first stuck on `PerfLevel::Disable` forever
+    // (the usual pool of grpc/raftstore/scheduler worker threads in a real tikv-server).
+    // Track "have I raised it on *this* thread yet" instead.
+    static PERF_LEVEL_ENABLED: Cell<bool> = Cell::new(false);
+}
+
+impl PerfContextStats {
+    pub fn capture() -> PerfContextStats {
+        PERF_LEVEL_ENABLED.with(|enabled| {
+            if !enabled.get() {
+                set_perf_level(PerfLevel::EnableCount);
+                enabled.set(true);
+            }
+        });
+        let ctx = PerfContext::get();
+        PerfContextStats {
+            block_cache_hit_count: ctx.block_cache_hit_count(),
+            block_read_count: ctx.block_read_count(),
+            bloom_filter_checked: ctx.bloom_memtable_checked() + ctx.bloom_sst_checked(),
+            bloom_filter_useful: ctx.bloom_memtable_hit_count() + ctx.bloom_sst_hit_count(),
+            internal_key_skipped_count: ctx.internal_key_skipped_count(),
+            internal_delete_skipped_count: ctx.internal_delete_skipped_count(),
+        }
+    }
+
+    fn delta(self) -> PerfContextStats {
+        let now = PerfContextStats::capture();
+        PerfContextStats {
+            block_cache_hit_count: now
+                .block_cache_hit_count
+                .saturating_sub(self.block_cache_hit_count),
+            block_read_count: now.block_read_count.saturating_sub(self.block_read_count),
+            bloom_filter_checked: now
+                .bloom_filter_checked
+                .saturating_sub(self.bloom_filter_checked),
+            bloom_filter_useful: now
+                .bloom_filter_useful
+                .saturating_sub(self.bloom_filter_useful),
+            internal_key_skipped_count: now
+                .internal_key_skipped_count
+                .saturating_sub(self.internal_key_skipped_count),
+            internal_delete_skipped_count: now
+                .internal_delete_skipped_count
+                .saturating_sub(self.internal_delete_skipped_count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_perf_context_across_operation() {
+        let mut stat = CfStatistics::default();
+        let start = CfStatistics::start_perf_context();
+        // Stand in for the scan/get that would normally run between start and record; the
+        // delta folded in below must never underflow even if nothing happened in between.
+        stat.record_perf_context(start);
+        assert_eq!(stat.block_cache_hit_count, 0);
+        assert_eq!(stat.block_read_count, 0);
+        assert_eq!(stat.internal_key_skipped_count, 0);
+    }
+
+    #[test]
+    fn test_perf_level_enabled_on_every_thread() {
+        // Regression test: `set_perf_level` only affects the calling thread, so every thread
+        // that captures a PerfContext snapshot must raise its own perf level rather than
+        // relying on a single process-wide toggle that only the first caller ever hits.
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    PerfContextStats::capture();
+                    PERF_LEVEL_ENABLED.with(|enabled| enabled.get())
+                })
+            })
+            .collect();
+        for h in handles {
+            assert!(h.join().unwrap());
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use prometheus::*;
+use prometheus_static_metric::*;
+
+make_auto_flush_static_metric! {
+    pub label_enum GcKeysCF {
+        default,
+        lock,
+        write,
+    }
+
+    pub label_enum GcKeysDetail {
+        processed_keys,
+        get,
+        next,
+        prev,
+        seek,
+        seek_for_prev,
+        over_seek_bound,
+        block_cache_hit_count,
+        block_read_count,
+        bloom_filter_checked,
+        bloom_filter_useful,
+        internal_key_skipped_count,
+        internal_delete_skipped_count,
+    }
+
+    pub struct GcKeysCounterVec: LocalIntCounter {
+        "cf" => GcKeysCF,
+        "tag" => GcKeysDetail,
+    }
+}
+
+lazy_static! {
+    pub static ref GC_KEYS_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "tikv_storage_gc_key_operations",
+        "Counter of gc keys handled",
+        &["cf", "tag"]
+    )
+    .unwrap();
+    pub static ref GC_KEYS_COUNTER_VEC_STATIC: GcKeysCounterVec =
+        auto_flush_from!(GC_KEYS_COUNTER_VEC, GcKeysCounterVec);
+}